@@ -1,11 +1,19 @@
 use std::iter::Zip;
 
-use sfml::{cpp::FBox, graphics::{Color, Drawable, RcTexture, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Transformable}, system::Vector2i, window::{ContextSettings, Event, Key, Style}, SfResult};
+use sfml::{cpp::FBox, graphics::{Color, Drawable, RcTexture, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Transformable}, system::{Clock, Vector2i}, window::{mouse::Button, ContextSettings, Event, Key, Style}, SfResult};
 
-fn process_window_events(window: &mut FBox<RenderWindow>) -> () {
+fn process_window_events(window: &mut FBox<RenderWindow>, world: &mut World, selected_tool: &mut Box<dyn PlayerAction>) -> () {
     while let Some(ev) = window.poll_event() {
         match ev {
             Event::Closed => window.close(),
+            Event::KeyPressed { code: Key::Num1, .. } => *selected_tool = Box::new(Hoe),
+            Event::KeyPressed { code: Key::Num2, .. } => *selected_tool = Box::new(Seed),
+            Event::KeyPressed { code: Key::Num3, .. } => *selected_tool = Box::new(WateringCan),
+            Event::MouseButtonPressed { button: Button::Left, x, y } => {
+                if let Some((tx, ty, tz)) = world.pick_tile(Vector2i::new(x, y)) {
+                    selected_tool.apply(world, tx, ty, tz);
+                }
+            }
             _ => {}
         }
     }
@@ -15,29 +23,60 @@ fn process_window_events(window: &mut FBox<RenderWindow>) -> () {
 enum Tile {
     Air,
     Grass,
-    Plant(bool),
+    Dirt,
+    Plant { stage: u8, watered: bool },
+    Farmland(bool),
 }
 
 impl Tile {
     pub const WIDTH_PX: u32 = 32;
     pub const HEIGHT_PX: u32 = 32;
     pub const ATLAS_LINE_TILE_COUNT: u32 = 16;
+    pub const PLANT_MAX_STAGE: u8 = 3;
     pub fn tile_id(&self) -> i32 {
         match &self {
             Self::Air => 0,
             Self::Grass => 1,
-            Self::Plant(false) => 2,
-            Self::Plant(true) => 3
+            Self::Dirt => 2,
+            Self::Plant { stage, .. } => 3 + *stage as i32,
+            Self::Farmland(false) => 3 + Self::PLANT_MAX_STAGE as i32 + 1,
+            Self::Farmland(true) => 3 + Self::PLANT_MAX_STAGE as i32 + 2,
         }
     }
-    pub fn texture_rect(&self) -> Option<Rect<i32>> {
+    // Grass is a connecting tile: `edge_mask` selects one of 16 border
+    // sprites from a dedicated atlas row instead of a fixed cell. Other
+    // tiles ignore the mask and use their plain `tile_id` cell.
+    pub const GRASS_AUTOTILE_ROW: i32 = 1;
+
+    // Each atlas cell now reserves this many horizontal variants so ground
+    // tiles don't all look identical. `variant` is picked deterministically
+    // per-cell (see `tile_variant_hash`), not re-rolled per frame.
+    pub const VARIANT_COUNT: u32 = 4;
+
+    pub fn texture_rect(&self, edge_mask: u8, variant: u32) -> Option<Rect<i32>> {
+        let variant = variant % Self::VARIANT_COUNT;
         match &self {
             Self::Air => None,
+            Self::Grass => {
+                let column = edge_mask as i32 * Self::VARIANT_COUNT as i32 + variant as i32;
+                Some(Rect::new(
+                    Self::WIDTH_PX as i32 * column,
+                    Self::HEIGHT_PX as i32 * Self::GRASS_AUTOTILE_ROW,
+                    Self::WIDTH_PX as i32,
+                    Self::HEIGHT_PX as i32
+                ))
+            }
             _ => {
                 let a = self.tile_id();
+                let column = (a % Self::ATLAS_LINE_TILE_COUNT as i32) * Self::VARIANT_COUNT as i32 + variant as i32;
+                let row = a / Self::ATLAS_LINE_TILE_COUNT as i32;
+                // Row `GRASS_AUTOTILE_ROW` is reserved for the grass autotile
+                // sprites above; push plain tile rows past it so a future
+                // tile id can't silently land on the same row.
+                let row = if row >= Self::GRASS_AUTOTILE_ROW { row + 1 } else { row };
                 Some(Rect::new(
-                    Self::WIDTH_PX as i32 * (a % Self::ATLAS_LINE_TILE_COUNT as i32),
-                    Self::HEIGHT_PX as i32 * (a / Self::ATLAS_LINE_TILE_COUNT as i32),
+                    Self::WIDTH_PX as i32 * column,
+                    Self::HEIGHT_PX as i32 * row,
                     Self::WIDTH_PX as i32,
                     Self::HEIGHT_PX as i32
                 ))
@@ -46,8 +85,86 @@ impl Tile {
     }
 }
 
+// Hashes a tile's grid coordinate and the world seed into a stable variant
+// index, so the same cell always picks the same atlas variant.
+fn tile_variant_hash(x: i64, y: i64, z: i64, seed: u64) -> u32 {
+    let mut rng = Rng(seed
+        ^ (x as u64).wrapping_mul(0x9E3779B97F4A7C15)
+        ^ (y as u64).wrapping_mul(0x165667B19E3779F9)
+        ^ (z as u64).wrapping_mul(0xC2B2AE3D27D4EB4F));
+    (rng.next_u64() % Tile::VARIANT_COUNT as u64) as u32
+}
+
+// Precomputes the per-cell variant for an entire world up front so lookups
+// during drawing are a plain index instead of a hash.
+fn build_variants(size_x: usize, size_y: usize, size_z: usize, seed: u64) -> Vec<u32> {
+    let mut variants = vec![0u32; size_x * size_y * size_z];
+    for z in 0..size_z as i64 {
+        for y in 0..size_y as i64 {
+            for x in 0..size_x as i64 {
+                let index = x as usize + (y as usize * size_x) + (z as usize * size_x * size_y);
+                variants[index] = tile_variant_hash(x, y, z, seed);
+            }
+        }
+    }
+    variants
+}
+
+// Small deterministic PRNG (splitmix64) so a seed reproduces the same map.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Hashes a grid coordinate into a reproducible pseudo-random value in [0, 1).
+fn lattice_value(x: i64, z: i64, seed: u64) -> f64 {
+    let mut rng = Rng(seed
+        ^ (x as u64).wrapping_mul(0x9E3779B97F4A7C15)
+        ^ (z as u64).wrapping_mul(0xC2B2AE3D27D4EB4F));
+    rng.next_f64()
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn smoothstep(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Value noise over the (x, z) plane, sampled at integer lattice points and
+// smoothly interpolated in between. Returns roughly [-1, 1].
+fn height_noise(x: i64, z: i64, seed: u64) -> f64 {
+    const CELL: i64 = 6;
+    let cell_x = x.div_euclid(CELL);
+    let cell_z = z.div_euclid(CELL);
+    let tx = smoothstep((x.rem_euclid(CELL)) as f64 / CELL as f64);
+    let tz = smoothstep((z.rem_euclid(CELL)) as f64 / CELL as f64);
+
+    let v00 = lattice_value(cell_x, cell_z, seed);
+    let v10 = lattice_value(cell_x + 1, cell_z, seed);
+    let v01 = lattice_value(cell_x, cell_z + 1, seed);
+    let v11 = lattice_value(cell_x + 1, cell_z + 1, seed);
+
+    let top = lerp(v00, v10, tx);
+    let bottom = lerp(v01, v11, tx);
+    lerp(top, bottom, tz) * 2.0 - 1.0
+}
+
 struct World {
     tiles: Vec<Tile>,
+    variants: Vec<u32>,
     size_x: usize,
     size_y: usize,
     size_z: usize,
@@ -70,18 +187,133 @@ impl World {
     pub fn get_tile(&self, x: i64, y: i64, z: i64) -> Option<Tile> {
         self.get_index(x, y, z).map(|index| self.tiles[index])
     }
-    
-    pub fn new_flat(size_x: usize, size_y: usize, size_z: usize) -> Self {
+
+    // Stable per-cell atlas variant, chosen at generation time from a hash
+    // of the coordinate and the world's seed.
+    pub fn get_variant(&self, x: i64, y: i64, z: i64) -> u32 {
+        self.get_index(x, y, z).map(|index| self.variants[index]).unwrap_or(0)
+    }
+
+    // True when the three neighbors that occlude this tile under the
+    // isometric projection are all solid, i.e. the tile can never be seen.
+    // A tile on a boundary face (a neighbor missing) is never hidden.
+    pub fn is_tile_hidden(&self, x: i64, y: i64, z: i64) -> bool {
+        let neighbors = [
+            self.get_tile(x + 1, y, z),
+            self.get_tile(x, y, z + 1),
+            self.get_tile(x, y + 1, z),
+        ];
+        neighbors.iter().all(|n| matches!(n, Some(t) if !matches!(t, Tile::Air)))
+    }
+
+    // 4-bit mask of which of the +x, -x, +z, -z neighbors share this tile's
+    // surface type, used to pick a border sprite for connecting tiles. Only
+    // grass connects, so the mask is 0 for every other tile.
+    pub fn tile_edge_mask(&self, x: i64, y: i64, z: i64) -> u8 {
+        let Some(tile) = self.get_tile(x, y, z) else { return 0 };
+        let connects = |other: Option<Tile>| matches!((tile, other), (Tile::Grass, Some(Tile::Grass)));
+
+        let mut mask = 0u8;
+        if connects(self.get_tile(x + 1, y, z)) { mask |= 1 << 0; }
+        if connects(self.get_tile(x - 1, y, z)) { mask |= 1 << 1; }
+        if connects(self.get_tile(x, y, z + 1)) { mask |= 1 << 2; }
+        if connects(self.get_tile(x, y, z - 1)) { mask |= 1 << 3; }
+        mask
+    }
+
+    // Screen-to-grid picking: walks layers from the top down and returns the
+    // coordinates of the first non-air tile under the clicked pixel.
+    pub fn pick_tile(&self, px: Vector2i) -> Option<(i64, i64, i64)> {
+        for y in (0..self.size_y as i64).rev() {
+            let (x, z) = px_to_tile_xz(px, y);
+            if let Some(tile) = self.get_tile(x, y, z) {
+                if !matches!(tile, Tile::Air) {
+                    return Some((x, y, z));
+                }
+            }
+        }
+        None
+    }
+
+    // Builds terrain from a 2D noise field: each (x, z) column gets a height
+    // `h` derived from the noise, dirt fills the column up to `h - 1` and a
+    // single grass tile caps it at `h`.
+    pub fn new_generated(size_x: usize, size_y: usize, size_z: usize, seed: u64) -> Self {
         let tiles = vec![Tile::Air; size_x * size_y * size_z];
-        
-        let mut world = Self {tiles, size_x, size_y, size_z};
+        let variants = build_variants(size_x, size_y, size_z, seed);
+        let mut world = Self {tiles, variants, size_x, size_y, size_z};
+
         for x in 0..size_x as i64 {
-            for z in 0..size_z as i64{
-                world.set_tile(x, 0, z, Tile::Grass);
+            for z in 0..size_z as i64 {
+                let n = height_noise(x, z, seed);
+                let h = ((n * 0.5 + 0.5) * (size_y - 1) as f64) as i64;
+                let h = h.clamp(0, size_y as i64 - 1);
+
+                for y in 0..h {
+                    world.set_tile(x, y, z, Tile::Dirt);
+                }
+                world.set_tile(x, h, z, Tile::Grass);
             }
         }
         world
     }
+
+    // Advances plant growth by one fixed timestep. Watered plants consume
+    // the watered flag and move to the next stage; dry ones stall in place.
+    pub fn tick(&mut self, _dt: f32) {
+        for y in 0..self.size_y as i64 {
+            for x in 0..self.size_x as i64 {
+                for z in 0..self.size_z as i64 {
+                    if let Some(Tile::Plant { stage, watered: true }) = self.get_tile(x, y, z) {
+                        let stage = (stage + 1).min(Tile::PLANT_MAX_STAGE);
+                        self.set_tile(x, y, z, Tile::Plant { stage, watered: false });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A tool the player can use on a targeted tile.
+trait PlayerAction {
+    fn apply(&self, world: &mut World, x: i64, y: i64, z: i64);
+}
+
+// Tills grass into farmland.
+struct Hoe;
+
+impl PlayerAction for Hoe {
+    fn apply(&self, world: &mut World, x: i64, y: i64, z: i64) {
+        if let Some(Tile::Grass) = world.get_tile(x, y, z) {
+            world.set_tile(x, y, z, Tile::Farmland(false));
+        }
+    }
+}
+
+// Plants a seed in the column above tilled farmland.
+struct Seed;
+
+impl PlayerAction for Seed {
+    fn apply(&self, world: &mut World, x: i64, y: i64, z: i64) {
+        if let Some(Tile::Farmland(_)) = world.get_tile(x, y, z) {
+            if let Some(Tile::Air) = world.get_tile(x, y + 1, z) {
+                world.set_tile(x, y + 1, z, Tile::Plant { stage: 0, watered: false });
+            }
+        }
+    }
+}
+
+// Waters farmland or a plant, marking it watered.
+struct WateringCan;
+
+impl PlayerAction for WateringCan {
+    fn apply(&self, world: &mut World, x: i64, y: i64, z: i64) {
+        match world.get_tile(x, y, z) {
+            Some(Tile::Farmland(_)) => world.set_tile(x, y, z, Tile::Farmland(true)),
+            Some(Tile::Plant { stage, .. }) => world.set_tile(x, y, z, Tile::Plant { stage, watered: true }),
+            _ => {}
+        }
+    }
 }
 
 fn tile_coords_to_px(x: i64, y: i64, z: i64) -> Vector2i {
@@ -93,8 +325,21 @@ fn tile_coords_to_px(x: i64, y: i64, z: i64) -> Vector2i {
     )
 }
 
-fn draw_tile_at_px(tile: Tile, x: i64, y: i64, window: &mut FBox<RenderWindow>, tex_terrain: &RcTexture) {
-    if let Some(texture_rect) = tile.texture_rect() {
+// Inverse of `tile_coords_to_px` for a known `y` layer, taken about the centre
+// of a tile's top face (16, 8 px into its sprite): recovers the (x, z) grid
+// column under a screen pixel, rounding to the nearest tile.
+fn px_to_tile_xz(px: Vector2i, y: i64) -> (i64, i64) {
+    const TILE_SIZE: i64 = 32;
+    const QUARTER_UNIT: i64 = TILE_SIZE / 4;
+    let a = (px.x - (QUARTER_UNIT * 2) as i32) as f64 / (QUARTER_UNIT * 2) as f64;
+    let b = (px.y - QUARTER_UNIT as i32) as f64 / QUARTER_UNIT as f64 + (2 * y) as f64;
+    let x = ((a + b) / 2.0).round() as i64;
+    let z = ((b - a) / 2.0).round() as i64;
+    (x, z)
+}
+
+fn draw_tile_at_px(tile: Tile, edge_mask: u8, variant: u32, x: i64, y: i64, window: &mut FBox<RenderWindow>, tex_terrain: &RcTexture) {
+    if let Some(texture_rect) = tile.texture_rect(edge_mask, variant) {
         let mut r = RectangleShape::new();
         r.set_position((x as f32, y as f32));
         r.set_size((32.0, 32.0));
@@ -106,22 +351,35 @@ fn draw_tile_at_px(tile: Tile, x: i64, y: i64, window: &mut FBox<RenderWindow>,
     }
 }
 
-fn draw_tile_at_grid(tile: Tile, x: i64, y: i64, z: i64, window: &mut FBox<RenderWindow>, tex_terrain: &RcTexture) {
+fn draw_tile_at_grid(tile: Tile, edge_mask: u8, variant: u32, x: i64, y: i64, z: i64, window: &mut FBox<RenderWindow>, tex_terrain: &RcTexture) {
     let px = tile_coords_to_px(x, y, z);
-    draw_tile_at_px(tile, px.x as i64, px.y as i64, window, tex_terrain);
+    draw_tile_at_px(tile, edge_mask, variant, px.x as i64, px.y as i64, window, tex_terrain);
 }
 
 fn draw_window(window: &mut FBox<RenderWindow>, world: &World, tex_terrain: &RcTexture) -> () {
     window.clear(Color::BLACK);
 
+    // Painter's algorithm: draw in order of increasing depth key `x + z - y`,
+    // so tiles with a smaller `x + z` and a higher `y` are drawn first.
+    let mut coords: Vec<(i64, i64, i64)> = Vec::with_capacity(world.size_x * world.size_y * world.size_z);
     for y in 0..world.size_y as i64 {
         for x in 0..world.size_x as i64 {
             for z in 0..world.size_z as i64 {
-                let tile = world.get_tile(x, y, z).unwrap();
-                draw_tile_at_grid(tile, x, y, z, window, tex_terrain);
+                coords.push((x, y, z));
             }
         }
     }
+    coords.sort_by_key(|&(x, y, z)| x + z - y);
+
+    for (x, y, z) in coords {
+        if world.is_tile_hidden(x, y, z) {
+            continue;
+        }
+        let tile = world.get_tile(x, y, z).unwrap();
+        let edge_mask = world.tile_edge_mask(x, y, z);
+        let variant = world.get_variant(x, y, z);
+        draw_tile_at_grid(tile, edge_mask, variant, x, y, z, window, tex_terrain);
+    }
 
     // draw_tile_at_grid(Tile::Grass, 0, 0, 0, window, tex_terrain);
     // draw_tile_at_grid(Tile::Grass, 1, 0, 0, window, tex_terrain);
@@ -146,11 +404,23 @@ fn main() -> SfResult<()> {
     let mut window = RenderWindow::new((640, 480), "gardening", Style::DEFAULT, 
         &ContextSettings::default())?;
     window.set_vertical_sync_enabled(true);
-    let mut world = World::new_flat(5, 2, 10);
-    world.set_tile(4, 1, 2, Tile::Plant(true));
+    const WORLD_SEED: u64 = 1337;
+    let mut world = World::new_generated(5, 2, 10, WORLD_SEED);
+    world.set_tile(4, 1, 2, Tile::Plant { stage: 0, watered: false });
+    let mut selected_tool: Box<dyn PlayerAction> = Box::new(Hoe);
+
+    const FIXED_DT: f32 = 1.0 / 20.0;
+    let mut tick_clock = Clock::start();
+    let mut tick_accumulator: f32 = 0.0;
 
     while window.is_open() {
-        process_window_events(&mut window);
+        tick_accumulator += tick_clock.restart().as_seconds();
+        while tick_accumulator >= FIXED_DT {
+            world.tick(FIXED_DT);
+            tick_accumulator -= FIXED_DT;
+        }
+
+        process_window_events(&mut window, &mut world, &mut selected_tool);
         draw_window(&mut window, &world, &tex_terrain);
     }
 